@@ -1,55 +1,852 @@
-use serde::{Deserialize, Serialize};
-use std::error::Error;
-
-#[derive(Debug, Serialize, Deserialize)]
-struct AioContent {
-    aio_version: String,
-    content: Vec<Chunk>,
-    index: Vec<IndexItem>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Chunk {
-    id: String,
-    content: String,
-    hash: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct IndexItem {
-    id: String,
-    keywords: Option<Vec<String>>,
-    token_estimate: Option<u32>,
-}
-
-fn main() -> Result<(), Box<dyn Error>> {
-    println!("AIO Rust Parser Prototype");
-    println!("-------------------------");
-
-    let url = "http://localhost:8000/ai-content.aio";
-    let body = reqwest::blocking::get(url)?.text()?;
-
-    let aio: AioContent = serde_json::from_str(&body)?;
-
-    println!("Version: {}", aio.aio_version);
-    println!("Chunks: {}", aio.content.len());
-    
-    // Example targeted retrieval
-    let query = "pricing";
-    let mut relevant_tokens = 0;
-    
-    println!("\nSearching for '{}'...", query);
-    
-    for chunk in aio.content {
-        // Simplified search logic
-        if chunk.content.to_lowercase().contains(query) {
-            println!("MATCH: {}", chunk.id);
-            println!("Content: {:.100}...", chunk.content);
-            relevant_tokens += chunk.content.len() / 4;
-        }
-    }
-    
-    println!("\nTotal Relevant Tokens: {}", relevant_tokens);
-
-    Ok(())
-}
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AioContent {
+    aio_version: String,
+    content: Vec<Chunk>,
+    index: Vec<IndexItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Chunk {
+    id: String,
+    content: String,
+    hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexItem {
+    id: String,
+    keywords: Option<Vec<String>>,
+    token_estimate: Option<u32>,
+}
+
+/// BM25 tuning constants (standard defaults).
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+#[derive(Debug)]
+struct SearchResult {
+    chunk_id: String,
+    score: f64,
+}
+
+/// Inverted index over an `AioContent`'s chunks, built once and reused for
+/// every query against the same document.
+struct InvertedIndex {
+    /// term -> list of (chunk index, term frequency in that chunk)
+    postings: HashMap<String, Vec<(usize, u32)>>,
+    /// token length of each chunk, in the same order as `AioContent.content`
+    chunk_lengths: Vec<u32>,
+    avgdl: f64,
+}
+
+/// Lowercase and split on non-alphanumeric boundaries, dropping empty tokens.
+fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Token length of a chunk, preferring the index's `token_estimate` when one
+/// is available and falling back to an actual token count otherwise.
+fn chunk_token_len(chunk: &Chunk, index_item: Option<&IndexItem>) -> u32 {
+    if let Some(estimate) = index_item.and_then(|item| item.token_estimate) {
+        return estimate;
+    }
+    tokenize(&chunk.content).len() as u32
+}
+
+impl InvertedIndex {
+    fn build(aio: &AioContent) -> Self {
+        let index_by_id: HashMap<&str, &IndexItem> =
+            aio.index.iter().map(|item| (item.id.as_str(), item)).collect();
+
+        let mut postings: HashMap<String, Vec<(usize, u32)>> = HashMap::new();
+        let mut chunk_lengths = Vec::with_capacity(aio.content.len());
+
+        for (chunk_idx, chunk) in aio.content.iter().enumerate() {
+            let item = index_by_id.get(chunk.id.as_str()).copied();
+            chunk_lengths.push(chunk_token_len(chunk, item));
+
+            let mut term_freqs: HashMap<String, u32> = HashMap::new();
+            for term in tokenize(&chunk.content) {
+                *term_freqs.entry(term).or_insert(0) += 1;
+            }
+            if let Some(keywords) = item.and_then(|item| item.keywords.as_ref()) {
+                for keyword in keywords {
+                    for term in tokenize(keyword) {
+                        *term_freqs.entry(term).or_insert(0) += 1;
+                    }
+                }
+            }
+
+            for (term, freq) in term_freqs {
+                postings.entry(term).or_default().push((chunk_idx, freq));
+            }
+        }
+
+        let avgdl = if chunk_lengths.is_empty() {
+            0.0
+        } else {
+            chunk_lengths.iter().map(|&len| len as f64).sum::<f64>() / chunk_lengths.len() as f64
+        };
+
+        InvertedIndex { postings, chunk_lengths, avgdl }
+    }
+
+    fn idf(&self, term: &str) -> f64 {
+        let n = self.chunk_lengths.len() as f64;
+        let n_t = self.postings.get(term).map(|postings| postings.len()).unwrap_or(0) as f64;
+        ((n - n_t + 0.5) / (n_t + 0.5) + 1.0).ln()
+    }
+
+    /// Score every chunk containing at least one query term via Okapi BM25.
+    fn score(&self, query: &str) -> HashMap<usize, f64> {
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for term in tokenize(query) {
+            let Some(postings) = self.postings.get(&term) else { continue };
+            let idf = self.idf(&term);
+            for &(chunk_idx, tf) in postings {
+                let tf = tf as f64;
+                let dl = self.chunk_lengths[chunk_idx] as f64;
+                // avgdl is 0 only when every chunk has zero effective token
+                // length; skip length normalization rather than divide by zero.
+                let length_norm = if self.avgdl == 0.0 { 1.0 - BM25_B } else { 1.0 - BM25_B + BM25_B * dl / self.avgdl };
+                let denom = tf + BM25_K1 * length_norm;
+                let term_score = idf * (tf * (BM25_K1 + 1.0)) / denom;
+                *scores.entry(chunk_idx).or_insert(0.0) += term_score;
+            }
+        }
+        scores
+    }
+}
+
+/// Rank `aio`'s chunks against `query` using Okapi BM25 and return the
+/// top `top_k` by descending score. `index` must have been built from
+/// `aio` (callers share one cached index rather than rebuilding it per
+/// call — see [`AppState`]).
+fn search(index: &InvertedIndex, aio: &AioContent, query: &str, top_k: usize) -> Vec<SearchResult> {
+    let mut scored: Vec<SearchResult> = index
+        .score(query)
+        .into_iter()
+        .map(|(chunk_idx, score)| SearchResult { chunk_id: aio.content[chunk_idx].id.clone(), score })
+        .collect();
+
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+    scored.truncate(top_k);
+    scored
+}
+
+/// Result of packing chunks into a fixed token budget for a RAG prompt.
+#[derive(Debug, Serialize)]
+struct PackedContext {
+    chunk_ids: Vec<String>,
+    total_tokens: u32,
+    tokens_dropped: u32,
+}
+
+/// Token estimate for a chunk: the index's `token_estimate` when present,
+/// else `content.len() / 4` as a cheap approximation.
+fn estimate_tokens(chunk: &Chunk, index_item: Option<&IndexItem>) -> u32 {
+    index_item
+        .and_then(|item| item.token_estimate)
+        .unwrap_or((chunk.content.len() / 4) as u32)
+}
+
+/// Candidate count above which the exact knapsack DP is skipped in favor
+/// of the greedy pass (bounds the DP's time/memory independent of the
+/// capacity cap below).
+const KNAPSACK_DP_LIMIT: usize = 200;
+
+/// Select the best-scoring subset of chunks matching `query` that fits
+/// within `max_tokens`, for assembling a prompt that never overflows the
+/// model's context window.
+///
+/// Scores chunks via [`search`], then picks a subset with a value-density
+/// greedy pass (score / token_estimate, descending). When the candidate
+/// set is small enough and the budget is within [`knapsack_select`]'s
+/// capacity cap, refines that greedy pick with an exact 0/1 knapsack DP
+/// over the token budget.
+fn pack_context(index: &InvertedIndex, aio: &AioContent, query: &str, max_tokens: u32) -> PackedContext {
+    let index_by_id: HashMap<&str, &IndexItem> =
+        aio.index.iter().map(|item| (item.id.as_str(), item)).collect();
+    let chunks_by_id: HashMap<&str, &Chunk> =
+        aio.content.iter().map(|chunk| (chunk.id.as_str(), chunk)).collect();
+
+    let candidates: Vec<(String, u32, f64)> = search(index, aio, query, aio.content.len())
+        .into_iter()
+        .filter(|result| result.score > 0.0)
+        .map(|result| {
+            let chunk = chunks_by_id[result.chunk_id.as_str()];
+            let tokens = estimate_tokens(chunk, index_by_id.get(chunk.id.as_str()).copied());
+            (result.chunk_id, tokens, result.score)
+        })
+        .collect();
+
+    let chosen: Vec<usize> = if candidates.len() <= KNAPSACK_DP_LIMIT {
+        knapsack_select(&candidates, max_tokens).unwrap_or_else(|| greedy_pack(&candidates, max_tokens))
+    } else {
+        greedy_pack(&candidates, max_tokens)
+    };
+
+    let total_tokens: u32 = chosen.iter().map(|&idx| candidates[idx].1).sum();
+    let all_tokens: u32 = candidates.iter().map(|(_, tokens, _)| tokens).sum();
+
+    PackedContext {
+        chunk_ids: chosen.into_iter().map(|idx| candidates[idx].0.clone()).collect(),
+        total_tokens,
+        tokens_dropped: all_tokens - total_tokens,
+    }
+}
+
+/// Value-density greedy pass: sort candidates by score/token_estimate
+/// descending and take while the budget allows. Used directly for large
+/// candidate sets, and as the fallback when [`knapsack_select`] declines
+/// a budget that's too large to DP over.
+fn greedy_pack(candidates: &[(String, u32, f64)], max_tokens: u32) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by(|&a, &b| {
+        let density_a = candidates[a].2 / candidates[a].1.max(1) as f64;
+        let density_b = candidates[b].2 / candidates[b].1.max(1) as f64;
+        density_b.partial_cmp(&density_a).unwrap()
+    });
+
+    let mut budget = max_tokens;
+    let mut chosen = Vec::new();
+    for idx in order {
+        let tokens = candidates[idx].1;
+        if tokens <= budget {
+            chosen.push(idx);
+            budget -= tokens;
+        }
+    }
+    chosen
+}
+
+/// Hard cap on the knapsack DP's capacity (tokens), independent of
+/// `max_tokens`. `max_tokens` is a client-supplied value on the public
+/// `/search` endpoint with no server-side ceiling; without this, a caller
+/// requesting a large context window (completely normal given modern
+/// model sizes) makes the `(n+1) * (capacity+1)` DP table grow unbounded,
+/// which is a memory-exhaustion vector on every request that sets it.
+const KNAPSACK_MAX_CAPACITY: usize = 20_000;
+
+/// Exact 0/1 knapsack over token budget (capacity) maximizing total score,
+/// scaling fractional scores to integer "value" units for the DP table.
+/// Returns `None` when the resulting DP table would exceed
+/// [`KNAPSACK_MAX_CAPACITY`], so the caller can fall back to
+/// [`greedy_pack`] instead of allocating an unbounded table.
+fn knapsack_select(candidates: &[(String, u32, f64)], max_tokens: u32) -> Option<Vec<usize>> {
+    const SCALE: f64 = 1000.0;
+    let weights: Vec<usize> = candidates.iter().map(|(_, tokens, _)| *tokens as usize).collect();
+    // The DP table is O(n * capacity); a budget far larger than the total
+    // candidate weight (e.g. a million-token window over a handful of small
+    // chunks) wastes memory for no benefit, since everything fits anyway.
+    let total_weight: usize = weights.iter().sum();
+    let capacity = (max_tokens as usize).min(total_weight);
+    if capacity > KNAPSACK_MAX_CAPACITY {
+        return None;
+    }
+    let values: Vec<i64> = candidates.iter().map(|(_, _, score)| (score * SCALE) as i64).collect();
+
+    let n = candidates.len();
+    let mut dp = vec![vec![0i64; capacity + 1]; n + 1];
+    for i in 1..=n {
+        for w in 0..=capacity {
+            dp[i][w] = dp[i - 1][w];
+            if weights[i - 1] <= w {
+                dp[i][w] = dp[i][w].max(dp[i - 1][w - weights[i - 1]] + values[i - 1]);
+            }
+        }
+    }
+
+    let mut chosen = Vec::new();
+    let mut w = capacity;
+    for i in (1..=n).rev() {
+        if dp[i][w] != dp[i - 1][w] {
+            chosen.push(i - 1);
+            w -= weights[i - 1];
+        }
+    }
+    chosen.reverse();
+    Some(chosen)
+}
+
+/// A chunk whose stored `hash` did not match its recomputed content digest.
+#[derive(Debug)]
+struct HashMismatch {
+    chunk_id: String,
+    expected: String,
+    actual: String,
+}
+
+/// Failure returned by [`verify_chunks`].
+#[derive(Debug)]
+enum IntegrityError {
+    Mismatches(Vec<HashMismatch>),
+    UnknownAlgorithm(String),
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityError::Mismatches(mismatches) => {
+                writeln!(f, "{} chunk(s) failed integrity verification:", mismatches.len())?;
+                for mismatch in mismatches {
+                    writeln!(
+                        f,
+                        "  {}: expected {}, got {}",
+                        mismatch.chunk_id, mismatch.expected, mismatch.actual
+                    )?;
+                }
+                Ok(())
+            }
+            IntegrityError::UnknownAlgorithm(algorithm) => {
+                write!(f, "unknown hash algorithm: {}", algorithm)
+            }
+        }
+    }
+}
+
+impl Error for IntegrityError {}
+
+/// Compute a chunk content digest, returning it as a lowercase hex string.
+fn compute_digest(algorithm: &str, content: &str) -> Result<String, IntegrityError> {
+    match algorithm {
+        "sha256" => {
+            let mut hasher = Sha256::new();
+            hasher.update(content.as_bytes());
+            Ok(format!("{:x}", hasher.finalize()))
+        }
+        "blake3" => Ok(blake3::hash(content.as_bytes()).to_hex().to_string()),
+        other => Err(IntegrityError::UnknownAlgorithm(other.to_string())),
+    }
+}
+
+/// Split a `Chunk.hash` value into its algorithm and expected digest,
+/// defaulting to SHA-256 when the hash carries no `algo:` prefix.
+fn parse_hash(hash: &str) -> (&str, &str) {
+    hash.split_once(':').unwrap_or(("sha256", hash))
+}
+
+/// Recompute each chunk's content digest and compare it against the
+/// `Chunk.hash` recorded in the `.aio` file, catching corrupted or
+/// tampered content before it's served. Returns every mismatching chunk
+/// id rather than failing on the first one.
+fn verify_chunks(aio: &AioContent) -> Result<(), IntegrityError> {
+    let mut mismatches = Vec::new();
+    for chunk in &aio.content {
+        let (algorithm, expected) = parse_hash(&chunk.hash);
+        let actual = compute_digest(algorithm, &chunk.content)?;
+        if actual != expected {
+            mismatches.push(HashMismatch {
+                chunk_id: chunk.id.clone(),
+                expected: expected.to_string(),
+                actual,
+            });
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(IntegrityError::Mismatches(mismatches))
+    }
+}
+
+const DEFAULT_AIO_URL: &str = "http://localhost:8000/ai-content.aio";
+
+/// The `.aio` source URL: `AIO_URL` when set, else [`DEFAULT_AIO_URL`].
+fn aio_url_from_env() -> String {
+    std::env::var("AIO_URL").unwrap_or_else(|_| DEFAULT_AIO_URL.to_string())
+}
+
+/// How to authenticate a remote `.aio` fetch.
+enum Auth {
+    /// Sent as `x-api-key: <key>`.
+    ApiKey(String),
+    /// Sent as `Authorization: Bearer <token>`.
+    Bearer(String),
+}
+
+/// Read auth from the environment: `AIO_API_KEY` for an `x-api-key` header,
+/// or `AIO_BEARER_TOKEN` for an `Authorization: Bearer` header.
+fn auth_from_env() -> Option<Auth> {
+    if let Ok(key) = std::env::var("AIO_API_KEY") {
+        return Some(Auth::ApiKey(key));
+    }
+    if let Ok(token) = std::env::var("AIO_BEARER_TOKEN") {
+        return Some(Auth::Bearer(token));
+    }
+    None
+}
+
+/// Fetch and parse an `.aio` document from `url`, authenticating with
+/// `auth` when present. Async so it can be embedded directly in async
+/// services, such as the search server below.
+async fn load_aio(url: &str, auth: Option<&Auth>) -> Result<AioContent, Box<dyn Error>> {
+    let client = reqwest::Client::builder().gzip(true).brotli(true).build()?;
+
+    let mut request = client.get(url);
+    request = match auth {
+        Some(Auth::ApiKey(key)) => request.header("x-api-key", key),
+        Some(Auth::Bearer(token)) => request.header("Authorization", format!("Bearer {}", token)),
+        None => request,
+    };
+
+    let body = request.send().await?.text().await?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+fn default_limit() -> usize {
+    25
+}
+
+fn default_crop_length() -> usize {
+    40
+}
+
+fn default_highlight_start() -> String {
+    "<em>".to_string()
+}
+
+fn default_highlight_end() -> String {
+    "</em>".to_string()
+}
+
+/// Query parameters accepted by both the `GET` and `POST /search` routes.
+#[derive(Debug, Deserialize)]
+struct SearchParams {
+    q: String,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_limit")]
+    limit: usize,
+    /// Comma-separated keywords; a chunk must carry all of them in its
+    /// `IndexItem.keywords` to be included.
+    keywords: Option<String>,
+    /// Number of words kept in the cropped snippet, centered on the match.
+    #[serde(default = "default_crop_length")]
+    crop_length: usize,
+    /// Marker inserted before each matched span, e.g. `<em>`.
+    #[serde(default = "default_highlight_start")]
+    highlight_start: String,
+    /// Marker inserted after each matched span, e.g. `</em>`.
+    #[serde(default = "default_highlight_end")]
+    highlight_end: String,
+    /// Treat `q` as a regex rather than a literal substring when matching
+    /// spans to highlight.
+    #[serde(default)]
+    regex: bool,
+    /// When set, also return a token-budget-packed subset of the matched
+    /// chunks (see [`pack_context`]) sized to fit this many tokens.
+    max_tokens: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchResultDto {
+    chunk_id: String,
+    content: String,
+    snippet: String,
+    score: f64,
+    token_estimate: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchResponse {
+    total: usize,
+    offset: usize,
+    limit: usize,
+    results: Vec<SearchResultDto>,
+    packed: Option<PackedContext>,
+}
+
+/// Byte ranges of whitespace-delimited words in `content`, in order.
+fn word_spans(content: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in content.char_indices() {
+        if c.is_whitespace() {
+            if let Some(s) = start.take() {
+                spans.push((s, i));
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, content.len()));
+    }
+    spans
+}
+
+/// Byte ranges of every match of `query` against `content`: a regex search
+/// when `use_regex` is set, otherwise a case-insensitive literal search.
+fn match_spans(content: &str, query: &str, use_regex: bool) -> Result<Vec<(usize, usize)>, regex::Error> {
+    if use_regex {
+        let re = regex::Regex::new(query)?;
+        return Ok(re.find_iter(content).map(|m| (m.start(), m.end())).collect());
+    }
+
+    let lower_content = content.to_lowercase();
+    let lower_query = query.to_lowercase();
+    if lower_query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut spans = Vec::new();
+    let mut from = 0;
+    while let Some(offset) = lower_content[from..].find(&lower_query) {
+        let start = from + offset;
+        let end = start + lower_query.len();
+        spans.push((start, end));
+        from = end;
+    }
+    Ok(spans)
+}
+
+/// Crop a chunk's content to a `crop_length`-word window centered on the
+/// first match of `query`, wrapping every matched span in `highlight_start`
+/// / `highlight_end` markers. Falls back to a plain leading crop when
+/// there's no match.
+fn crop_and_highlight(
+    content: &str,
+    query: &str,
+    crop_length: usize,
+    highlight_start: &str,
+    highlight_end: &str,
+    use_regex: bool,
+) -> Result<String, regex::Error> {
+    let words = word_spans(content);
+    let spans = match_spans(content, query, use_regex)?;
+
+    let (byte_start, byte_end) = if words.is_empty() {
+        (0, content.len())
+    } else if let Some(&(match_start, _)) = spans.first() {
+        let center = words
+            .iter()
+            .position(|&(s, e)| s <= match_start && match_start < e)
+            .unwrap_or(0);
+        let window = crop_length.max(1);
+        let half = window / 2;
+        let start_idx = center.saturating_sub(half);
+        let end_idx = (start_idx + window).min(words.len());
+        let start_idx = end_idx.saturating_sub(window).min(start_idx);
+        (words[start_idx].0, words[end_idx.saturating_sub(1)].1)
+    } else {
+        let end_idx = crop_length.min(words.len());
+        (words[0].0, words[end_idx.saturating_sub(1)].1)
+    };
+
+    let mut out = String::new();
+    if byte_start > 0 {
+        out.push_str("...");
+    }
+
+    let mut cursor = byte_start;
+    for &(match_start, match_end) in &spans {
+        if match_end <= byte_start || match_start >= byte_end {
+            continue;
+        }
+        let clamped_start = match_start.max(byte_start);
+        let clamped_end = match_end.min(byte_end);
+        out.push_str(&content[cursor..clamped_start]);
+        out.push_str(highlight_start);
+        out.push_str(&content[clamped_start..clamped_end]);
+        out.push_str(highlight_end);
+        cursor = clamped_end;
+    }
+    out.push_str(&content[cursor..byte_end]);
+
+    if byte_end < content.len() {
+        out.push_str("...");
+    }
+
+    Ok(out)
+}
+
+/// Shared state handed to every request handler: the parsed `.aio`
+/// document and its BM25 inverted index, both loaded/built once at
+/// startup and cached for the life of the process rather than redone on
+/// every request.
+#[derive(Clone)]
+struct AppState {
+    aio: std::sync::Arc<AioContent>,
+    index: std::sync::Arc<InvertedIndex>,
+}
+
+/// Core handler shared by the `GET` and `POST /search` routes.
+fn run_search(state: &AppState, params: &SearchParams) -> Result<SearchResponse, regex::Error> {
+    let aio = &state.aio;
+    let index = &state.index;
+    let index_by_id: HashMap<&str, &IndexItem> =
+        aio.index.iter().map(|item| (item.id.as_str(), item)).collect();
+    let chunks_by_id: HashMap<&str, &Chunk> =
+        aio.content.iter().map(|chunk| (chunk.id.as_str(), chunk)).collect();
+
+    // An empty or whitespace-only `keywords` param (e.g. `?keywords=` or
+    // `?keywords=, ,`) means "no filter", not "require zero keywords" —
+    // the latter would otherwise vacuously match every chunk with keyword
+    // metadata and reject every chunk without it.
+    let keyword_filter: Option<Vec<String>> = params.keywords.as_ref().and_then(|raw| {
+        let required: Vec<String> =
+            raw.split(',').map(|kw| kw.trim().to_lowercase()).filter(|kw| !kw.is_empty()).collect();
+        if required.is_empty() {
+            None
+        } else {
+            Some(required)
+        }
+    });
+
+    let mut results = search(index, aio, &params.q, aio.content.len());
+    if let Some(required) = &keyword_filter {
+        results.retain(|result| {
+            let chunk_keywords = index_by_id
+                .get(result.chunk_id.as_str())
+                .and_then(|item| item.keywords.as_ref());
+            match chunk_keywords {
+                Some(keywords) => {
+                    let lowered: Vec<String> = keywords.iter().map(|kw| kw.to_lowercase()).collect();
+                    required.iter().all(|req| lowered.contains(req))
+                }
+                None => false,
+            }
+        });
+    }
+
+    let total = results.len();
+    let page: Vec<SearchResultDto> = results
+        .into_iter()
+        .skip(params.offset)
+        .take(params.limit)
+        .map(|result| {
+            let chunk = chunks_by_id[result.chunk_id.as_str()];
+            let token_estimate =
+                estimate_tokens(chunk, index_by_id.get(chunk.id.as_str()).copied());
+            let snippet = crop_and_highlight(
+                &chunk.content,
+                &params.q,
+                params.crop_length,
+                &params.highlight_start,
+                &params.highlight_end,
+                params.regex,
+            )?;
+            Ok(SearchResultDto {
+                chunk_id: result.chunk_id,
+                content: chunk.content.clone(),
+                snippet,
+                score: result.score,
+                token_estimate,
+            })
+        })
+        .collect::<Result<Vec<_>, regex::Error>>()?;
+
+    let packed = params.max_tokens.map(|max_tokens| pack_context(index, aio, &params.q, max_tokens));
+
+    Ok(SearchResponse { total, offset: params.offset, limit: params.limit, results: page, packed })
+}
+
+fn search_response(
+    state: &AppState,
+    params: &SearchParams,
+) -> Result<axum::Json<SearchResponse>, (axum::http::StatusCode, String)> {
+    run_search(state, params)
+        .map(axum::Json)
+        .map_err(|err| (axum::http::StatusCode::BAD_REQUEST, format!("invalid query regex: {}", err)))
+}
+
+async fn search_get(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<SearchParams>,
+) -> Result<axum::Json<SearchResponse>, (axum::http::StatusCode, String)> {
+    search_response(&state, &params)
+}
+
+async fn search_post(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::Json(params): axum::Json<SearchParams>,
+) -> Result<axum::Json<SearchResponse>, (axum::http::StatusCode, String)> {
+    search_response(&state, &params)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    println!("AIO Rust Search Service");
+    println!("------------------------");
+
+    let verify = !std::env::args().any(|arg| arg == "--no-verify");
+    let auth = auth_from_env();
+    let url = aio_url_from_env();
+
+    let aio = load_aio(&url, auth.as_ref()).await?;
+
+    if verify {
+        verify_chunks(&aio)?;
+        println!("Integrity check passed for {} chunk(s)", aio.content.len());
+    } else {
+        println!("Skipping integrity check (--no-verify)");
+    }
+
+    let index = InvertedIndex::build(&aio);
+    let state = AppState { aio: std::sync::Arc::new(aio), index: std::sync::Arc::new(index) };
+    let app = axum::Router::new()
+        .route("/search", axum::routing::get(search_get).post(search_post))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
+    println!("Listening on http://0.0.0.0:8080 (GET/POST /search)");
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aio_with_chunks(chunks: Vec<(&str, &str)>) -> AioContent {
+        AioContent {
+            aio_version: "1.0".to_string(),
+            content: chunks
+                .into_iter()
+                .map(|(id, content)| Chunk { id: id.to_string(), content: content.to_string(), hash: String::new() })
+                .collect(),
+            index: Vec::new(),
+        }
+    }
+
+    fn aio_with_chunk_hash(content: &str, hash: &str) -> AioContent {
+        AioContent {
+            aio_version: "1.0".to_string(),
+            content: vec![Chunk { id: "a".to_string(), content: content.to_string(), hash: hash.to_string() }],
+            index: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn crop_and_highlight_handles_zero_crop_length() {
+        let snippet = crop_and_highlight("hello world foo bar", "hello", 0, "<em>", "</em>", false)
+            .expect("literal match should not error");
+        assert!(snippet.contains("<em>hello</em>"));
+    }
+
+    #[test]
+    fn crop_and_highlight_wraps_every_match() {
+        let snippet = crop_and_highlight("foo bar foo baz", "foo", 10, "<em>", "</em>", false).unwrap();
+        assert_eq!(snippet.matches("<em>foo</em>").count(), 2);
+    }
+
+    #[test]
+    fn crop_and_highlight_supports_regex_queries() {
+        let snippet = crop_and_highlight("order id 482, order id 910", r"\d+", 10, "[", "]", true).unwrap();
+        assert!(snippet.contains("[482]"));
+    }
+
+    #[test]
+    fn crop_and_highlight_reports_invalid_regex() {
+        let result = crop_and_highlight("hello world", "(unclosed", 10, "<em>", "</em>", true);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn search_ranks_chunks_with_more_query_term_matches_first() {
+        let aio = aio_with_chunks(vec![
+            ("a", "pricing pricing pricing plans"),
+            ("b", "an unrelated chunk about widgets"),
+            ("c", "pricing details for enterprise plans"),
+        ]);
+
+        let index = InvertedIndex::build(&aio);
+        let results = search(&index, &aio, "pricing", 3);
+        assert_eq!(results[0].chunk_id, "a");
+        assert!(results.iter().all(|r| r.chunk_id != "b" || r.score == 0.0));
+    }
+
+    #[test]
+    fn search_does_not_panic_when_all_chunk_lengths_are_zero() {
+        // Empty content but keyword-only postings drive every chunk's
+        // effective length (and so avgdl) to zero.
+        let aio = AioContent {
+            aio_version: "1.0".to_string(),
+            content: vec![
+                Chunk { id: "a".to_string(), content: String::new(), hash: String::new() },
+                Chunk { id: "b".to_string(), content: String::new(), hash: String::new() },
+            ],
+            index: vec![
+                IndexItem { id: "a".to_string(), keywords: Some(vec!["pricing".to_string()]), token_estimate: None },
+                IndexItem { id: "b".to_string(), keywords: Some(vec!["pricing".to_string()]), token_estimate: None },
+            ],
+        };
+
+        let index = InvertedIndex::build(&aio);
+        let results = search(&index, &aio, "pricing", 2);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.score.is_finite()));
+    }
+
+    #[test]
+    fn parse_hash_defaults_to_sha256_when_untagged() {
+        assert_eq!(parse_hash("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"), ("sha256", "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"));
+    }
+
+    #[test]
+    fn parse_hash_splits_an_algorithm_prefix() {
+        assert_eq!(parse_hash("blake3:deadbeef"), ("blake3", "deadbeef"));
+    }
+
+    #[test]
+    fn compute_digest_matches_known_sha256_vector() {
+        let digest = compute_digest("sha256", "hello").unwrap();
+        assert_eq!(digest, "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+    }
+
+    #[test]
+    fn compute_digest_rejects_unknown_algorithm() {
+        let err = compute_digest("md5", "hello").unwrap_err();
+        assert!(matches!(err, IntegrityError::UnknownAlgorithm(algorithm) if algorithm == "md5"));
+    }
+
+    #[test]
+    fn verify_chunks_passes_for_an_untagged_sha256_match() {
+        let aio = aio_with_chunk_hash(
+            "hello",
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+        );
+        assert!(verify_chunks(&aio).is_ok());
+    }
+
+    #[test]
+    fn verify_chunks_passes_for_a_blake3_tagged_match() {
+        let expected = blake3::hash(b"hello").to_hex().to_string();
+        let aio = aio_with_chunk_hash("hello", &format!("blake3:{}", expected));
+        assert!(verify_chunks(&aio).is_ok());
+    }
+
+    #[test]
+    fn verify_chunks_reports_every_mismatching_chunk() {
+        let aio = AioContent {
+            aio_version: "1.0".to_string(),
+            content: vec![
+                Chunk { id: "a".to_string(), content: "hello".to_string(), hash: "deadbeef".to_string() },
+                Chunk { id: "b".to_string(), content: "world".to_string(), hash: "deadbeef".to_string() },
+            ],
+            index: Vec::new(),
+        };
+
+        match verify_chunks(&aio).unwrap_err() {
+            IntegrityError::Mismatches(mismatches) => {
+                assert_eq!(mismatches.len(), 2);
+                assert_eq!(mismatches[0].chunk_id, "a");
+                assert_eq!(mismatches[1].chunk_id, "b");
+            }
+            other => panic!("expected Mismatches, got {:?}", other),
+        }
+    }
+}